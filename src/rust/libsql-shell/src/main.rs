@@ -4,6 +4,8 @@ use clap::Parser;
 use rusqlite::{types::ValueRef, Connection, Statement};
 use rustyline::error::ReadlineError;
 use rustyline::DefaultEditor;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 #[derive(Debug, Parser)]
 #[command(name = "libsql")]
@@ -24,18 +26,135 @@ fn format_value(v: ValueRef) -> String {
     }
 }
 
-// Executes a libSQL statement
-// TODO: introduce paging for presenting large results, get rid of Vec
-fn execute(stmt: &mut Statement) -> Result<Vec<Vec<String>>> {
-    let column_count = stmt.column_count();
+// Maps a libSQL value onto its natural JSON representation. Blobs have no JSON
+// counterpart, so they are encoded with the same base64 alphabet as format_value.
+fn value_to_json(v: ValueRef) -> serde_json::Value {
+    use serde_json::Value;
+    match v {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(r) => Value::from(r),
+        ValueRef::Text(s) => Value::from(std::str::from_utf8(s).unwrap()),
+        ValueRef::Blob(b) => Value::from(general_purpose::STANDARD_NO_PAD.encode(b)),
+    }
+}
+
+// Quotes a single field following RFC 4180: a field is wrapped in double quotes
+// only when it contains a quote, comma or line break, and embedded quotes are doubled.
+fn csv_quote(field: &str) -> String {
+    if field.contains(['"', ',', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+// The output format used to render result rows, switched at runtime with `.mode`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OutputMode {
+    Table,
+    List,
+    Csv,
+    Json,
+}
 
+impl std::fmt::Display for OutputMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            OutputMode::Table => "table",
+            OutputMode::List => "list",
+            OutputMode::Csv => "csv",
+            OutputMode::Json => "json",
+        };
+        f.write_str(name)
+    }
+}
+
+// Session-wide shell settings threaded through statement execution.
+struct ShellState {
+    mode: OutputMode,
+    pagesize: usize,
+    // Shared with the `profile` callback registered on the connection so the
+    // engine's own per-statement timing is printed only while `.timer` is on.
+    timer: Arc<AtomicBool>,
+    eqp: bool,
+}
+
+impl ShellState {
+    fn new() -> Self {
+        ShellState {
+            mode: OutputMode::Table,
+            pagesize: 25,
+            timer: Arc::new(AtomicBool::new(false)),
+            eqp: false,
+        }
+    }
+}
+
+// Returns true for statements that the query planner can explain, i.e. SELECT
+// and the data-modifying statements.
+fn is_explainable(statement: &str) -> bool {
+    let keyword = statement
+        .trim_start()
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_ascii_uppercase();
+    matches!(keyword.as_str(), "SELECT" | "INSERT" | "UPDATE" | "DELETE" | "WITH")
+}
+
+// Prints the query plan tree produced by EXPLAIN QUERY PLAN for a statement.
+fn explain_query_plan(connection: &Connection, statement: &str) {
+    let mut stmt = match connection.prepare(&format!("EXPLAIN QUERY PLAN {statement}")) {
+        Ok(stmt) => stmt,
+        Err(e) => {
+            println!("Error: {e}");
+            return;
+        }
+    };
     let rows = stmt.query_map((), |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(3)?))
+    });
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(e) => {
+            println!("Error: {e}");
+            return;
+        }
+    };
+    let rows = rows.flatten().collect::<Vec<(i64, i64, String)>>();
+    // The engine reports each node's id and its parent's id (0 at the root);
+    // walk the parent chain to find how deep each node sits and indent it one
+    // level per ancestor so the plan renders as a tree.
+    let parents = rows
+        .iter()
+        .map(|(id, parent, _)| (*id, *parent))
+        .collect::<std::collections::HashMap<i64, i64>>();
+    println!("QUERY PLAN");
+    for (_id, parent, detail) in &rows {
+        let mut depth = 0;
+        let mut ancestor = *parent;
+        while ancestor != 0 {
+            depth += 1;
+            ancestor = parents.get(&ancestor).copied().unwrap_or(0);
+        }
+        println!("{}`--{}", "   ".repeat(depth), detail);
+    }
+}
+
+// Executes a libSQL statement, yielding one formatted row at a time. The rows
+// are produced lazily so the caller can page through arbitrarily large results
+// without buffering them all in memory.
+fn execute(stmt: &mut Statement) -> Result<impl Iterator<Item = Vec<String>> + '_> {
+    let column_count = stmt.column_count();
+
+    let rows = stmt.query_map((), move |row| {
         let row = (0..column_count)
             .map(|idx| format_value(row.get_ref(idx).unwrap()))
             .collect::<Vec<String>>();
         Ok(row)
     })?;
-    Ok(rows.map(|r| r.unwrap()).collect())
+    Ok(rows.map(|r| r.unwrap()))
 }
 
 struct StrStatements {
@@ -72,8 +191,125 @@ fn get_str_statements(str: String) -> StrStatements {
     StrStatements { value: str }
 }
 
-fn run_statement(connection: &Connection, statement: String) {
+// Renders a single buffered page of rows in the text-table formats.
+fn render_page(column_names: &[String], page: &[Vec<String>], mode: OutputMode) {
+    match mode {
+        OutputMode::List => {
+            for row in page {
+                println!("{}", row.join("|"));
+            }
+        }
+        _ => {
+            let mut builder = tabled::builder::Builder::new();
+            builder.set_columns(column_names.to_vec());
+            for row in page {
+                builder.add_record(row.clone());
+            }
+            let mut table = builder.build();
+            table.with(tabled::Style::psql());
+            println!("{table}")
+        }
+    }
+}
+
+// Prompts `--More--` on a terminal and waits for the user before the next page.
+// Returns false when the user asks to stop, and always continues when stdin is
+// not a terminal so piped output is never interrupted. The pause is driven
+// through the editor that owns the terminal rather than reading stdin from
+// underneath it; `q` stops, anything else continues.
+fn prompt_more(rl: &mut DefaultEditor) -> bool {
+    use std::io::IsTerminal;
+    if !std::io::stdin().is_terminal() {
+        return true;
+    }
+    match rl.readline("--More-- ") {
+        Ok(line) => !line.trim().eq_ignore_ascii_case("q"),
+        Err(_) => false,
+    }
+}
+
+// Streams an iterator of formatted rows a page at a time, rendering each page in
+// `mode` and pausing for the user between pages once `pagesize` rows are buffered.
+// A `pagesize` of 0 disables paging: rows are rendered incrementally without
+// pausing, so memory stays flat regardless of the result size.
+fn paginate(
+    column_names: &[String],
+    rows: impl Iterator<Item = Vec<String>>,
+    mode: OutputMode,
+    pagesize: usize,
+    rl: &mut DefaultEditor,
+) {
+    let flush_at = if pagesize == 0 { 1 } else { pagesize };
+    let mut page: Vec<Vec<String>> = Vec::new();
+    for row in rows {
+        page.push(row);
+        if page.len() == flush_at {
+            render_page(column_names, &page, mode);
+            page.clear();
+            if pagesize != 0 && !prompt_more(rl) {
+                return;
+            }
+        }
+    }
+    if !page.is_empty() {
+        render_page(column_names, &page, mode);
+    }
+}
+
+// Renders the rows of a prepared statement using the session's current output mode.
+fn print_rows(stmt: &mut Statement, state: &ShellState, rl: &mut DefaultEditor) -> Result<()> {
+    let column_names = stmt
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<String>>();
+    match state.mode {
+        OutputMode::Table | OutputMode::List => {
+            let rows = execute(stmt)?;
+            paginate(&column_names, rows, state.mode, state.pagesize, rl);
+        }
+        OutputMode::Csv => {
+            let column_count = stmt.column_count();
+            // A column-less statement (INSERT, CREATE, ...) has no header or
+            // rows to emit; printing the empty header would leave a stray line.
+            if column_count > 0 {
+                println!(
+                    "{}",
+                    column_names
+                        .iter()
+                        .map(|name| csv_quote(name))
+                        .collect::<Vec<String>>()
+                        .join(",")
+                );
+            }
+            let mut rows = stmt.query(())?;
+            while let Some(row) = rows.next()? {
+                let fields = (0..column_count)
+                    .map(|idx| csv_quote(&format_value(row.get_ref(idx).unwrap())))
+                    .collect::<Vec<String>>();
+                println!("{}", fields.join(","));
+            }
+        }
+        OutputMode::Json => {
+            let column_count = stmt.column_count();
+            let mut rows = stmt.query(())?;
+            while let Some(row) = rows.next()? {
+                let mut object = serde_json::Map::new();
+                for idx in 0..column_count {
+                    object.insert(column_names[idx].clone(), value_to_json(row.get_ref(idx).unwrap()));
+                }
+                println!("{}", serde_json::Value::Object(object));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_statement(connection: &Connection, statement: String, state: &ShellState, rl: &mut DefaultEditor) {
     for str_statement in get_str_statements(statement) {
+        if state.eqp && is_explainable(&str_statement) {
+            explain_query_plan(connection, &str_statement);
+        }
         let mut stmt = match connection.prepare(&str_statement) {
             Ok(stmt) => stmt,
             Err(e) => {
@@ -81,40 +317,352 @@ fn run_statement(connection: &Connection, statement: String) {
                 continue;
             }
         };
-        let rows = match execute(&mut stmt) {
-            Ok(rows) => rows,
-            Err(e) => {
-                println!("Error: {e}");
-                continue;
-            }
-        };
-        if rows.is_empty() {
-            continue;
-        }
-        let mut builder = tabled::builder::Builder::new();
-        builder.set_columns(stmt.column_names());
-        for row in rows {
-            builder.add_record(row);
+        if let Err(e) = print_rows(&mut stmt, state, rl) {
+            println!("Error: {e}");
         }
-        let mut table = builder.build();
-        table.with(tabled::Style::psql());
-        println!("{table}")
     }
 }
 
-fn list_tables(pattern: Option<&str>, connection: &Connection) {
+fn list_tables(pattern: Option<&str>, connection: &Connection, state: &ShellState, rl: &mut DefaultEditor) {
     let mut statement = String::from("SELECT name FROM sqlite_schema WHERE type ='table' AND name NOT LIKE 'sqlite_%'");
     match pattern {
         Some(p) => statement.push_str(format!("AND name LIKE {p};").as_str()),
         None => statement.push(';')
     }
-    run_statement(connection, statement)
+    run_statement(connection, statement, state, rl)
 }
 
-fn run_command(command: &str, args: Vec<&str>, connection: &Connection) {
+// Drives a rusqlite backup handle to completion one bounded step at a time,
+// pausing briefly between steps so the source connection stays usable, and
+// reporting the copy progress as `remaining/total` pages.
+fn copy_database(backup: &rusqlite::backup::Backup) -> Result<()> {
+    const PAGES_PER_STEP: std::os::raw::c_int = 100;
+    loop {
+        let more = backup.step(PAGES_PER_STEP)?;
+        let progress = backup.progress();
+        println!("{}/{} pages remaining", progress.remaining, progress.pagecount);
+        match more {
+            rusqlite::backup::StepResult::Done => break,
+            rusqlite::backup::StepResult::More
+            | rusqlite::backup::StepResult::Busy
+            | rusqlite::backup::StepResult::Locked => {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Hot-copies the live connection into a fresh database at `path`.
+fn backup_to(connection: &Connection, path: &str) -> Result<()> {
+    let mut dst = Connection::open(path)?;
+    let backup = rusqlite::backup::Backup::new(connection, &mut dst)?;
+    copy_database(&backup)
+}
+
+// Escapes a string for use inside a single-quoted SQL literal.
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// Escapes an identifier for interpolation into SQL by double-quoting it and
+// doubling any embedded quote.
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+// Restores the database captured at `path` into the live connection. An online
+// backup needs exclusive `&mut` access to its destination, which cannot coexist
+// with a `.session` recorder that borrows the connection for its lifetime, so
+// the snapshot is replayed over the shared handle instead: the source database
+// is attached, the current contents are cleared, and its schema and rows are
+// copied back in dependency order.
+fn restore_from(connection: &Connection, path: &str) -> Result<()> {
+    connection.execute_batch(&format!(
+        "ATTACH DATABASE {} AS restore_src;",
+        quote_literal(path)
+    ))?;
+    // ATTACH cannot run inside a transaction, so wrap only the destructive
+    // drop/recreate: if any step fails partway the whole restore rolls back to
+    // the original contents rather than leaving a half-restored database.
+    let result = connection
+        .execute_batch("BEGIN;")
+        .map_err(anyhow::Error::from)
+        .and_then(|()| restore_attached(connection));
+    match &result {
+        Ok(()) => {
+            connection.execute_batch("COMMIT;").ok();
+        }
+        Err(_) => {
+            connection.execute_batch("ROLLBACK;").ok();
+        }
+    }
+    connection.execute_batch("DETACH DATABASE restore_src;").ok();
+    result
+}
+
+// Replays every user object and table row from the attached `restore_src`
+// database onto the main one, replacing whatever was there before.
+fn restore_attached(connection: &Connection) -> Result<()> {
+    let objects = |db: &str| -> Result<Vec<(String, String, Option<String>)>> {
+        let mut stmt = connection.prepare(&format!(
+            "SELECT type, name, sql FROM {db}.sqlite_schema WHERE name NOT LIKE 'sqlite_%'"
+        ))?;
+        let rows = stmt
+            .query_map((), |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(rows)
+    };
+    // Drop the current contents so the restore replaces rather than merges.
+    // Views go first as they may depend on tables; a dropped table takes its
+    // own indexes and triggers with it.
+    for (kind, name, _) in objects("main")?.iter().filter(|(k, ..)| k == "view") {
+        connection.execute_batch(&format!("DROP VIEW IF EXISTS main.{};", quote_ident(name)))?;
+        let _ = kind;
+    }
+    for (kind, name, _) in objects("main")?.iter().filter(|(k, ..)| k == "table") {
+        connection.execute_batch(&format!("DROP TABLE IF EXISTS main.{};", quote_ident(name)))?;
+        let _ = kind;
+    }
+    let source = objects("restore_src")?;
+    for (_, _, sql) in source.iter().filter(|(k, ..)| k == "table") {
+        if let Some(sql) = sql {
+            connection.execute_batch(&format!("{sql};"))?;
+        }
+    }
+    for (_, name, _) in source.iter().filter(|(k, ..)| k == "table") {
+        connection.execute(
+            &format!(
+                "INSERT INTO main.{table} SELECT * FROM restore_src.{table}",
+                table = quote_ident(name)
+            ),
+            (),
+        )?;
+    }
+    for (_, _, sql) in source.iter().filter(|(k, ..)| k != "table") {
+        if let Some(sql) = sql {
+            connection.execute_batch(&format!("{sql};"))?;
+        }
+    }
+    Ok(())
+}
+
+// Serializes the changeset accumulated so far by a running session into `path`.
+fn session_save(session: &mut rusqlite::session::Session, path: &str) -> Result<()> {
+    let mut file = std::fs::File::create(path)?;
+    session.changeset_strm(&mut file)?;
+    Ok(())
+}
+
+// Replays a previously captured changeset against the live connection, omitting
+// any rows that conflict with the current contents.
+fn changeset_apply(connection: &Connection, path: &str) -> Result<()> {
+    let mut file = std::fs::File::open(path)?;
+    connection.apply_strm(
+        &mut file,
+        None::<fn(&str) -> bool>,
+        |_conflict, _item| rusqlite::session::ConflictAction::SQLITE_CHANGESET_OMIT,
+    )?;
+    Ok(())
+}
+
+// Bulk-loads a CSV file into `table`, letting the csvtab virtual table handle
+// parsing and quoting. The target table is created from the CSV header when it
+// does not already exist.
+fn import_csv(connection: &Connection, file: &str, table: &str) -> Result<()> {
+    rusqlite::vtab::csvtab::load_module(connection)?;
+    let vtab = quote_ident(&format!("__import_{table}"));
+    let table = quote_ident(table);
+    connection.execute_batch(&format!(
+        "CREATE VIRTUAL TABLE temp.{vtab} USING csv(filename={}, header=YES);",
+        quote_literal(file)
+    ))?;
+    let columns = {
+        let stmt = connection.prepare(&format!("SELECT * FROM temp.{vtab}"))?;
+        stmt.column_names()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<String>>()
+    };
+    let column_defs = columns
+        .iter()
+        .map(|column| format!("{} TEXT", quote_ident(column)))
+        .collect::<Vec<String>>()
+        .join(", ");
+    connection.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {table} ({column_defs});"
+    ))?;
+    connection.execute(
+        &format!("INSERT INTO {table} SELECT * FROM temp.{vtab}"),
+        (),
+    )?;
+    connection.execute_batch(&format!("DROP TABLE temp.{vtab};"))?;
+    Ok(())
+}
+
+// Writes the rows of a table or query to a CSV file with RFC-4180 quoting.
+fn export_csv(connection: &Connection, source: &str, file: &str) -> Result<()> {
+    use std::io::Write;
+    let query = if source.trim_start().to_ascii_uppercase().starts_with("SELECT") {
+        source.to_owned()
+    } else {
+        format!("SELECT * FROM {}", quote_ident(source))
+    };
+    let mut stmt = connection.prepare(&query)?;
+    let column_count = stmt.column_count();
+    let column_names = stmt
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<String>>();
+    let mut out = std::fs::File::create(file)?;
+    writeln!(
+        out,
+        "{}",
+        column_names
+            .iter()
+            .map(|name| csv_quote(name))
+            .collect::<Vec<String>>()
+            .join(",")
+    )?;
+    let mut rows = stmt.query(())?;
+    while let Some(row) = rows.next()? {
+        let fields = (0..column_count)
+            .map(|idx| csv_quote(&format_value(row.get_ref(idx).unwrap())))
+            .collect::<Vec<String>>();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+// Loads a runtime extension with extension loading enabled only for the duration
+// of the call, so the surface stays closed outside the explicit `.load`.
+fn load_extension(connection: &Connection, path: &str, entrypoint: Option<&str>) -> Result<()> {
+    unsafe {
+        connection.load_extension_enable()?;
+        let result = connection.load_extension(path, entrypoint);
+        connection.load_extension_disable()?;
+        result?;
+    }
+    Ok(())
+}
+
+fn run_command<'conn>(
+    command: &str,
+    args: Vec<&str>,
+    connection: &'conn Connection,
+    state: &mut ShellState,
+    session: &mut Option<rusqlite::session::Session<'conn>>,
+    rl: &mut DefaultEditor,
+) {
     match command {
         "quit" => std::process::exit(0),
-        "tables" => list_tables(args.get(0).copied(), connection),
+        "tables" => list_tables(args.get(0).copied(), connection, state, rl),
+        "session" => match args.get(0).copied() {
+            Some("start") => match rusqlite::session::Session::new(connection) {
+                Ok(mut new_session) => match new_session.attach(None) {
+                    Ok(()) => *session = Some(new_session),
+                    Err(e) => println!("Error: {e}"),
+                },
+                Err(e) => println!("Error: {e}"),
+            },
+            // Dropping the session detaches it and frees the recorder.
+            Some("stop") => *session = None,
+            Some("save") => match (session.as_mut(), args.get(1).copied()) {
+                (Some(session), Some(path)) => {
+                    if let Err(e) = session_save(session, path) {
+                        println!("Error: {e}");
+                    }
+                }
+                (None, _) => println!("Error: no session is being recorded"),
+                (_, None) => println!("Error: usage: .session save <file>"),
+            },
+            _ => println!("Error: usage: .session <start|stop|save <file>>"),
+        },
+        "changeset" => match (args.get(0).copied(), args.get(1).copied()) {
+            (Some("apply"), Some(path)) => {
+                if let Err(e) = changeset_apply(connection, path) {
+                    println!("Error: {e}");
+                }
+            }
+            _ => println!("Error: usage: .changeset apply <file>"),
+        },
+        "backup" => match args.get(0).copied() {
+            Some(path) => {
+                if let Err(e) = backup_to(connection, path) {
+                    println!("Error: {e}");
+                }
+            }
+            None => println!("Error: usage: .backup <file>"),
+        },
+        "restore" => match args.get(0).copied() {
+            Some(path) => {
+                if let Err(e) = restore_from(connection, path) {
+                    println!("Error: {e}");
+                }
+            }
+            None => println!("Error: usage: .restore <file>"),
+        },
+        "mode" => match args.get(0).copied() {
+            Some("table") => state.mode = OutputMode::Table,
+            Some("list") => state.mode = OutputMode::List,
+            Some("csv") => state.mode = OutputMode::Csv,
+            Some("json") => state.mode = OutputMode::Json,
+            Some(mode) => println!("Error: unknown output mode '{mode}'"),
+            None => println!("current output mode: {}", state.mode),
+        },
+        "pagesize" => match args.get(0).copied() {
+            Some(size) => match size.parse::<usize>() {
+                Ok(size) => state.pagesize = size,
+                Err(_) => println!("Error: invalid page size '{size}'"),
+            },
+            None => println!("current page size: {}", state.pagesize),
+        },
+        "timer" => match args.get(0).copied() {
+            Some("on") => state.timer.store(true, Ordering::Relaxed),
+            Some("off") => state.timer.store(false, Ordering::Relaxed),
+            _ => println!("Error: usage: .timer on|off"),
+        },
+        "eqp" => match args.get(0).copied() {
+            Some("on") => state.eqp = true,
+            Some("off") => state.eqp = false,
+            _ => println!("Error: usage: .eqp on|off"),
+        },
+        "import" => match (args.get(0).copied(), args.get(1).copied()) {
+            (Some(file), Some(table)) => {
+                if let Err(e) = import_csv(connection, file, table) {
+                    println!("Error: {e}");
+                }
+            }
+            _ => println!("Error: usage: .import <file.csv> <table>"),
+        },
+        "export" => match (args.get(0).copied(), args.get(1).copied()) {
+            (Some(source), Some(file)) => {
+                if let Err(e) = export_csv(connection, source, file) {
+                    println!("Error: {e}");
+                }
+            }
+            _ => println!("Error: usage: .export <table|query> <file.csv>"),
+        },
+        "load" => match args.get(0).copied() {
+            Some(path) => {
+                let entrypoint = args.get(1).copied();
+                match load_extension(connection, path, entrypoint) {
+                    Ok(()) => println!(
+                        "Loaded extension '{path}' (entrypoint: {})",
+                        entrypoint.unwrap_or("default")
+                    ),
+                    Err(e) => println!("Error: {e}"),
+                }
+            }
+            None => println!("Error: usage: .load <shared-lib> [entrypoint]"),
+        },
         _ => println!("Unknown command '{}'", command)
     }
 }
@@ -130,7 +678,7 @@ fn main() -> Result<()> {
     rl.load_history(history.as_path()).ok();
 
     println!("libSQL version 0.2.0");
-    let connection = match args.db_path.as_deref() {
+    let mut connection = match args.db_path.as_deref() {
         None | Some("") | Some(":memory:") => {
             println!("Connected to a transient in-memory database.");
             Connection::open_in_memory()?
@@ -138,6 +686,17 @@ fn main() -> Result<()> {
         Some(path) => Connection::open(path)?,
     };
 
+    let mut state = ShellState::new();
+    // Report the engine's own measurement of each executed statement. The
+    // callback is registered once up front so the connection is free to be
+    // shared-borrowed afterwards; `.timer` only flips the flag it observes.
+    let timer = state.timer.clone();
+    connection.profile(Some(move |_sql: &str, duration: std::time::Duration| {
+        if timer.load(Ordering::Relaxed) {
+            println!("Run Time: real {:.3}", duration.as_secs_f64());
+        }
+    }));
+    let mut session: Option<rusqlite::session::Session> = None;
     let mut leftovers = String::new();
     loop {
         let prompt = if leftovers.is_empty() {
@@ -159,11 +718,11 @@ fn main() -> Result<()> {
                 if line.starts_with('.') {
                     let cmd: String = line[1..].to_string();
                     match cmd.split_once(' ') {
-                        Some((command, args)) => run_command(&command, args.split_whitespace().collect(), &connection),
-                        None => run_command(&cmd, Vec::new(), &connection)
+                        Some((command, args)) => run_command(&command, args.split_whitespace().collect(), &connection, &mut state, &mut session, &mut rl),
+                        None => run_command(&cmd, Vec::new(), &connection, &mut state, &mut session, &mut rl)
                     };
                 } else {
-                    run_statement(&connection, line)
+                    run_statement(&connection, line, &state, &mut rl)
                 }
             }
             Err(ReadlineError::Interrupted) => {
@@ -201,4 +760,31 @@ mod tests {
         );
         assert_eq!(str_statements_iterator.next(), None);
     }
+
+    #[test]
+    fn test_csv_quote_rfc_4180() {
+        assert_eq!(csv_quote("plain"), "plain");
+        assert_eq!(csv_quote("a,b"), "\"a,b\"");
+        assert_eq!(csv_quote("with\nbreak"), "\"with\nbreak\"");
+        assert_eq!(csv_quote("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_value_to_json_mapping() {
+        use serde_json::Value;
+        assert_eq!(value_to_json(ValueRef::Null), Value::Null);
+        assert_eq!(value_to_json(ValueRef::Integer(7)), Value::from(7));
+        assert_eq!(value_to_json(ValueRef::Text(b"hi")), Value::from("hi"));
+        // Blobs have no JSON counterpart and fall back to base64 text.
+        assert_eq!(value_to_json(ValueRef::Blob(&[1, 2, 3])), Value::from("AQID"));
+    }
+
+    #[test]
+    fn test_is_explainable_keyword() {
+        assert!(is_explainable("SELECT 1"));
+        assert!(is_explainable("  insert into t values (1)"));
+        assert!(is_explainable("WITH cte AS (SELECT 1) SELECT * FROM cte"));
+        assert!(!is_explainable("CREATE TABLE t (a)"));
+        assert!(!is_explainable(""));
+    }
 }